@@ -1,8 +1,16 @@
-use actix_web::{web, App, HttpResponse, HttpServer, middleware};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, middleware};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use hmac::Mac;
+use lru::LruCache;
+use rand::seq::SliceRandom;
 use rayon::prelude::*;
 use log::{info, error, warn, debug};
+use metrics_exporter_prometheus::PrometheusHandle;
 
 // Performance: Use mimalloc as global allocator for better performance
 #[global_allocator]
@@ -19,8 +27,212 @@ mod rom {
 use hashengine::hash as sh_hash;
 use rom::{RomGenerationType, Rom};
 
-// Global ROM state using RwLock to allow reinitialization for new challenges
-static ROM: once_cell::sync::Lazy<RwLock<Option<Arc<Rom>>>> = once_cell::sync::Lazy::new(|| RwLock::new(None));
+/// Identifies a ROM's content: a challenge is keyed on `no_pre_mine` plus the AshConfig
+/// fields that feed `Rom::new`, since the same `no_pre_mine` with different sizes/mixing
+/// produces a different ROM.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RomKey {
+    no_pre_mine: String,
+    pre_size: u32,
+    rom_size: u32,
+    mixing_numbers: u32,
+}
+
+fn rom_cache_capacity() -> NonZeroUsize {
+    std::env::var("ROM_CACHE_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .and_then(NonZeroUsize::new)
+        .unwrap_or(NonZeroUsize::new(4).unwrap())
+}
+
+// Bounded LRU of live ROMs so a node can hold several initialized challenges at once and
+// switch between them without paying the 5-10s regeneration cost. A tokio Mutex rather than
+// a RwLock because even a cache hit (`LruCache::get`) bumps recency and needs &mut access.
+static ROM_CACHE: once_cell::sync::Lazy<tokio::sync::Mutex<LruCache<RomKey, Arc<Rom>>>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(LruCache::new(rom_cache_capacity())));
+
+// Challenges with a build currently in flight, mapped to the job id doing the building.
+// Without this, a thundering herd of /init calls for the same uncached challenge (several
+// clients kicking off the same node at once is a realistic pattern) would each pass the
+// ROM_CACHE miss check and redundantly pay the 5-10s `Rom::new` cost. A request that finds
+// its key already here is told to poll the existing job instead of starting another.
+static ROM_BUILDS_IN_FLIGHT: once_cell::sync::Lazy<tokio::sync::Mutex<HashMap<RomKey, u64>>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+
+/// Monotonic id handed out for each /init call so callers can tell which background job
+/// a /health poll is reporting on.
+static NEXT_INIT_JOB_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RomInitStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+impl RomInitStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RomInitStatus::Pending => "pending",
+            RomInitStatus::Ready => "ready",
+            RomInitStatus::Failed => "failed",
+        }
+    }
+}
+
+fn rom_init_jobs_capacity() -> NonZeroUsize {
+    std::env::var("ROM_INIT_JOBS_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .and_then(NonZeroUsize::new)
+        .unwrap_or(NonZeroUsize::new(64).unwrap())
+}
+
+// Status of recent ROM init jobs started on this node, keyed by job id. Challenges can now
+// build concurrently (chunk0-4's cache), so a later /init for a different challenge must
+// not clobber an earlier job's outcome the way a single global slot would. Bounded LRU
+// rather than an unbounded map, same rationale as ROM_CACHE: a node gets /init'd repeatedly
+// for its whole lifetime, so every job needs to eventually fall off or this leaks forever.
+static ROM_INIT_JOBS: once_cell::sync::Lazy<RwLock<LruCache<u64, RomInitStatus>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(LruCache::new(rom_init_jobs_capacity())));
+
+/// Header set on /hash-batch sub-requests forwarded by a cluster peer. Its presence tells
+/// the receiving node to hash the shard locally rather than re-sharding across the cluster,
+/// which would otherwise fan out forever.
+const FORWARDED_HEADER: &str = "x-hashengine-forwarded";
+
+/// How often a node gossips its membership view to the cluster.
+const GOSSIP_INTERVAL_SECS: u64 = 5;
+/// Members not heard from within this many seconds are pruned and stop receiving work.
+const MEMBER_TIMEOUT_SECS: u64 = 30;
+/// Always gossip to this many known peers, plus a random third of whoever's left.
+const GOSSIP_FANOUT: usize = 3;
+/// The UDP gossip listener binds on the node's HTTP port plus this offset, so a bare
+/// HTTP address (as used for /hash-batch dispatch) is enough to find a peer's gossip port.
+const GOSSIP_PORT_OFFSET: u16 = 1000;
+/// Upper bound on a forwarded /hash-batch sub-request, a fraction of `MEMBER_TIMEOUT_SECS`
+/// so a peer that's alive but hung (as opposed to merely unreachable) still gets treated as
+/// a failed shard and falls back to local hashing instead of hanging the whole batch.
+const PEER_REQUEST_TIMEOUT_SECS: u64 = MEMBER_TIMEOUT_SECS / 3;
+
+/// A hex fingerprint of a `no_pre_mine` plus the AshConfig fields that determine ROM
+/// content, reused across the gossiped `PeerInfo` and `/health` so only a fixed-size
+/// fingerprint of the challenge ever goes over the wire. The config fields are included
+/// (not just the `no_pre_mine` fingerprint) so two distinct `RomKey`s that happen to share
+/// a `no_pre_mine` are never conflated into the same advertised challenge.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct ChallengeId {
+    #[serde(rename = "noPreMineFirst8")]
+    no_pre_mine_first8: String,
+    #[serde(rename = "noPreMineLast8")]
+    no_pre_mine_last8: String,
+    #[serde(rename = "preSize")]
+    pre_size: u32,
+    #[serde(rename = "romSize")]
+    rom_size: u32,
+    #[serde(rename = "mixingNumbers")]
+    mixing_numbers: u32,
+}
+
+fn challenge_id_for(key: &RomKey) -> ChallengeId {
+    let (no_pre_mine_first8, no_pre_mine_last8) = no_pre_mine_fingerprint(&key.no_pre_mine);
+    ChallengeId {
+        no_pre_mine_first8,
+        no_pre_mine_last8,
+        pre_size: key.pre_size,
+        rom_size: key.rom_size,
+        mixing_numbers: key.mixing_numbers,
+    }
+}
+
+fn no_pre_mine_fingerprint(no_pre_mine: &str) -> (String, String) {
+    let bytes = no_pre_mine.as_bytes();
+    (
+        hex::encode(&bytes[..bytes.len().min(8)]),
+        hex::encode(&bytes[bytes.len().saturating_sub(8)..]),
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PeerInfo {
+    addr: String,
+    last_seen: u64,
+    challenges: Vec<ChallengeId>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GossipMessage {
+    from: String,
+    members: Vec<PeerInfo>,
+    /// Hex HMAC-SHA256 of `from` + `members` under `GOSSIP_SHARED_SECRET`, absent when no
+    /// secret is configured. Gossip arrives over plain UDP with no source-address
+    /// authentication, so without this any host on the network could inject `PeerInfo`
+    /// entries (including a spoofed `addr`) that `matching_peers` would then trust with
+    /// real preimage batches.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    mac: Option<String>,
+}
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+/// `GOSSIP_SHARED_SECRET`, read once at startup. `None` leaves gossip unauthenticated
+/// (single-operator/trusted-network deployments), matching the absence of the env var.
+static GOSSIP_SHARED_SECRET: once_cell::sync::Lazy<Option<Vec<u8>>> =
+    once_cell::sync::Lazy::new(|| std::env::var("GOSSIP_SHARED_SECRET").ok().map(String::into_bytes));
+
+/// HMAC-SHA256 over the parts of a `GossipMessage` that drive cluster membership, so a
+/// tampered or forged message fails verification rather than being silently merged.
+fn gossip_mac(secret: &[u8], from: &str, members: &[PeerInfo]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(from.as_bytes());
+    for m in members {
+        mac.update(m.addr.as_bytes());
+        mac.update(&m.last_seen.to_le_bytes());
+        // `challenges` drives which peers `matching_peers` hands real preimage batches to,
+        // so it must be covered by the MAC too - otherwise an on-path attacker could leave
+        // addr/last_seen untouched and rewrite challenges on a legitimately-signed message
+        // without invalidating the signature.
+        for c in &m.challenges {
+            mac.update(c.no_pre_mine_first8.as_bytes());
+            mac.update(c.no_pre_mine_last8.as_bytes());
+            mac.update(&c.pre_size.to_le_bytes());
+            mac.update(&c.rom_size.to_le_bytes());
+            mac.update(&c.mixing_numbers.to_le_bytes());
+        }
+    }
+    mac
+}
+
+fn gossip_mac_hex(secret: &[u8], from: &str, members: &[PeerInfo]) -> String {
+    hex::encode(gossip_mac(secret, from, members).finalize().into_bytes())
+}
+
+/// Whether `msg` should be merged into `MEMBERS`: always true if no shared secret is
+/// configured (gossip auth is opt-in), otherwise only if its `mac` verifies against the
+/// secret (a constant-time comparison, since this is attacker-influenced input).
+fn gossip_message_authentic(msg: &GossipMessage) -> bool {
+    match GOSSIP_SHARED_SECRET.as_deref() {
+        None => true,
+        Some(secret) => match msg.mac.as_deref().map(hex::decode) {
+            Some(Ok(expected)) => gossip_mac(secret, &msg.from, &msg.members).verify_slice(&expected).is_ok(),
+            _ => false,
+        },
+    }
+}
+
+// Cluster membership, keyed by HTTP address. Reuses the same RwLock<..> pattern as ROM_INIT_JOBS.
+static MEMBERS: once_cell::sync::Lazy<RwLock<HashMap<String, PeerInfo>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(HashMap::new()));
+
+// A forwarded sub-request must time out well before the caller gives up, so a peer that's
+// alive but hung doesn't block the stitched /hash-batch response forever.
+static HTTP_CLIENT: once_cell::sync::Lazy<reqwest::Client> = once_cell::sync::Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(PEER_REQUEST_TIMEOUT_SECS))
+        .build()
+        .expect("failed to build peer HTTP client")
+});
 
 #[derive(Debug, Deserialize)]
 struct InitRequest {
@@ -45,11 +257,21 @@ struct InitResponse {
     status: String,
     worker_pid: u32,
     no_pre_mine: String,
+    #[serde(rename = "jobId")]
+    job_id: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct InitJobStatus {
+    #[serde(rename = "jobId")]
+    job_id: u64,
+    status: String,
 }
 
 #[derive(Debug, Deserialize)]
 struct HashRequest {
     preimage: String,
+    no_pre_mine: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -57,12 +279,13 @@ struct HashResponse {
     hash: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct BatchHashRequest {
     preimages: Vec<String>,
+    no_pre_mine: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct BatchHashResponse {
     hashes: Vec<String>,
 }
@@ -76,10 +299,14 @@ struct HealthResponse {
     native_available: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     config: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    no_pre_mine_first8: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    no_pre_mine_last8: Option<String>,
+    #[serde(rename = "romCacheSize")]
+    rom_cache_size: usize,
+    #[serde(rename = "romCacheCapacity")]
+    rom_cache_capacity: usize,
+    #[serde(rename = "cachedChallenges")]
+    cached_challenges: Vec<ChallengeId>,
+    #[serde(rename = "initJobs")]
+    init_jobs: Vec<InitJobStatus>,
 }
 
 #[derive(Debug, Serialize)]
@@ -87,90 +314,473 @@ struct ErrorResponse {
     error: String,
 }
 
-/// POST /init - Initialize ROM with challenge parameters
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// This node's own membership entry, or None if no ROM is cached yet (a node with nothing
+/// loaded has nothing to offer the cluster).
+async fn self_peer_info(self_addr: &str) -> Option<PeerInfo> {
+    let cache = ROM_CACHE.lock().await;
+    if cache.is_empty() {
+        return None;
+    }
+    let challenges = cache.iter().map(|(key, _)| challenge_id_for(key)).collect();
+    Some(PeerInfo {
+        addr: self_addr.to_string(),
+        last_seen: now_unix(),
+        challenges,
+    })
+}
+
+/// Result of looking up a cached ROM by `no_pre_mine` alone (the only thing /hash and
+/// /hash-batch callers send). `Ambiguous` means two cached challenges share this
+/// `no_pre_mine` with different AshConfigs, and there is no way to tell which ROM the
+/// caller means - returning either one could silently hash against the wrong challenge.
+enum RomLookup {
+    Found(Arc<Rom>),
+    NotFound,
+    Ambiguous,
+}
+
+/// The cached ROM for `no_pre_mine`, if this node has initialized exactly one challenge
+/// under that `no_pre_mine`. A hit promotes the entry to most-recently-used, same as any
+/// other cache lookup.
+async fn rom_for_no_pre_mine(no_pre_mine: &str) -> RomLookup {
+    let mut cache = ROM_CACHE.lock().await;
+    let mut matches: Vec<RomKey> = cache
+        .iter()
+        .filter(|(k, _)| k.no_pre_mine == no_pre_mine)
+        .map(|(k, _)| k.clone())
+        .collect();
+
+    match matches.len() {
+        0 => RomLookup::NotFound,
+        1 => match cache.get(&matches.remove(0)) {
+            Some(rom) => RomLookup::Found(Arc::clone(rom)),
+            None => RomLookup::NotFound,
+        },
+        _ => RomLookup::Ambiguous,
+    }
+}
+
+fn upsert_member(peer: PeerInfo) {
+    MEMBERS.write().unwrap().insert(peer.addr.clone(), peer);
+}
+
+fn merge_members(incoming: Vec<PeerInfo>) {
+    let mut members = MEMBERS.write().unwrap();
+    for peer in incoming {
+        members
+            .entry(peer.addr.clone())
+            .and_modify(|existing| {
+                if peer.last_seen > existing.last_seen {
+                    *existing = peer.clone();
+                }
+            })
+            .or_insert(peer);
+    }
+}
+
+fn prune_members() {
+    let now = now_unix();
+    let mut members = MEMBERS.write().unwrap();
+    members.retain(|_, m| now.saturating_sub(m.last_seen) <= MEMBER_TIMEOUT_SECS);
+}
+
+/// Peers (excluding self) gossiped to have the given challenge cached, i.e. safe to hand a
+/// shard of a /hash-batch request for that `no_pre_mine`. A `no_pre_mine`-only request can't
+/// name the full RomKey, so a peer is only considered a match if exactly one of its gossiped
+/// challenges carries this fingerprint - a peer with two configs sharing the fingerprint is
+/// just as ambiguous to us as it would be to itself, so it's excluded rather than guessed at.
+fn matching_peers(self_addr: &str, no_pre_mine: &str) -> Vec<PeerInfo> {
+    let target = no_pre_mine_fingerprint(no_pre_mine);
+    MEMBERS
+        .read()
+        .unwrap()
+        .values()
+        .filter(|m| {
+            if m.addr == self_addr {
+                return false;
+            }
+            let matches = m
+                .challenges
+                .iter()
+                .filter(|c| (c.no_pre_mine_first8.as_str(), c.no_pre_mine_last8.as_str()) == (target.0.as_str(), target.1.as_str()))
+                .count();
+            matches == 1
+        })
+        .cloned()
+        .collect()
+}
+
+/// Up to `GOSSIP_FANOUT` known peers plus a random third of whoever's left, so membership
+/// changes converge without every node gossiping to every other node every round.
+fn pick_gossip_targets(self_addr: &str) -> Vec<String> {
+    let mut others: Vec<String> = MEMBERS
+        .read()
+        .unwrap()
+        .keys()
+        .filter(|addr| addr.as_str() != self_addr)
+        .cloned()
+        .collect();
+
+    others.shuffle(&mut rand::thread_rng());
+
+    let head = GOSSIP_FANOUT.min(others.len());
+    let (primary, rest) = others.split_at(head);
+    let extra = rest.len() / 3;
+    let mut targets = primary.to_vec();
+    targets.extend(rest.iter().take(extra).cloned());
+    targets
+}
+
+fn gossip_addr_for(http_addr: &str) -> Option<String> {
+    let (host, port) = http_addr.rsplit_once(':')?;
+    let port: u16 = port.parse().ok()?;
+    // checked_add rather than wrapping_add: an HTTP port above 65535 - GOSSIP_PORT_OFFSET
+    // would otherwise silently wrap around to an unrelated low port instead of failing, and
+    // the existing "cannot derive gossip address" warning at the call site covers the None.
+    let gossip_port = port.checked_add(GOSSIP_PORT_OFFSET)?;
+    Some(format!("{}:{}", host, gossip_port))
+}
+
+async fn send_gossip(socket: &tokio::net::UdpSocket, target_http_addr: &str, self_addr: &str) {
+    let target = match gossip_addr_for(target_http_addr) {
+        Some(t) => t,
+        None => {
+            warn!("cannot derive gossip address for peer {}", target_http_addr);
+            return;
+        }
+    };
+    let members = MEMBERS.read().unwrap().values().cloned().collect::<Vec<_>>();
+    let mac = GOSSIP_SHARED_SECRET
+        .as_deref()
+        .map(|secret| gossip_mac_hex(secret, self_addr, &members));
+    let msg = GossipMessage { from: self_addr.to_string(), members, mac };
+    match serde_json::to_vec(&msg) {
+        Ok(bytes) => {
+            if let Err(e) = socket.send_to(&bytes, &target).await {
+                warn!("gossip send to {} failed: {}", target, e);
+            }
+        }
+        Err(e) => error!("failed to serialize gossip message: {}", e),
+    }
+}
+
+/// Runs for the lifetime of the process: listens for incoming gossip on the UDP socket and,
+/// on a timer, prunes dead members and gossips this node's view to a handful of peers.
+async fn run_gossip(self_addr: String, seeds: Vec<String>, socket: tokio::net::UdpSocket) {
+    let socket = Arc::new(socket);
+
+    for seed in &seeds {
+        send_gossip(&socket, seed, &self_addr).await;
+    }
+
+    let recv_socket = Arc::clone(&socket);
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 65536];
+        loop {
+            match recv_socket.recv_from(&mut buf).await {
+                Ok((len, src)) => match serde_json::from_slice::<GossipMessage>(&buf[..len]) {
+                    Ok(msg) if gossip_message_authentic(&msg) => merge_members(msg.members),
+                    Ok(msg) => warn!("dropping gossip from {} ({}): failed MAC verification", src, msg.from),
+                    Err(e) => warn!("malformed gossip message from {}: {}", src, e),
+                },
+                Err(e) => warn!("gossip recv error: {}", e),
+            }
+        }
+    });
+
+    let mut tick = tokio::time::interval(std::time::Duration::from_secs(GOSSIP_INTERVAL_SECS));
+    loop {
+        tick.tick().await;
+        prune_members();
+        if let Some(me) = self_peer_info(&self_addr).await {
+            upsert_member(me);
+        }
+        for target in pick_gossip_targets(&self_addr) {
+            send_gossip(&socket, &target, &self_addr).await;
+        }
+    }
+}
+
+/// Splits `preimages` across the local ROM (via rayon, as in the standalone path) and the
+/// given peers (via forwarded /hash-batch HTTP requests), then stitches the results back
+/// together in original order. A peer response is only trusted if it came back with a
+/// success status and the exact number of hashes the shard was sent with; anything else
+/// (an error status, a malformed body, or a short/long `hashes` array) is treated the same
+/// as an unreachable peer and hashed locally instead, so a flaky or misbehaving peer
+/// degrades throughput rather than dropping or misordering results.
+///
+/// Returns the stitched response alongside how many of `preimages` this node actually
+/// hashed itself (its own shard, plus any peer shard that fell back to local hashing) - the
+/// caller uses this instead of the full batch size for `hashengine_hashes_total` so a
+/// forwarded shard a peer *did* process isn't double-counted once by us and once by it.
+async fn shard_and_dispatch(
+    preimages: Vec<String>,
+    no_pre_mine: String,
+    rom: Arc<Rom>,
+    peers: Vec<PeerInfo>,
+) -> (HttpResponse, usize) {
+    let shard_count = peers.len() + 1;
+    let shard_size = (preimages.len() + shard_count - 1) / shard_count;
+    let mut shards: Vec<Vec<String>> = preimages
+        .chunks(shard_size.max(1))
+        .map(|c| c.to_vec())
+        .collect();
+    while shards.len() < shard_count {
+        shards.push(Vec::new());
+    }
+
+    let local_shard = shards.remove(0);
+    let locally_hashed = AtomicU64::new(local_shard.len() as u64);
+    let local_rom = Arc::clone(&rom);
+    let local_fut = async move {
+        local_shard
+            .par_iter()
+            .map(|preimage| hex::encode(sh_hash(preimage.as_bytes(), &local_rom, 8, 256)))
+            .collect::<Vec<String>>()
+    };
+
+    let remote_futs = peers.into_iter().zip(shards.into_iter()).map(|(peer, shard)| {
+        let fallback_rom = Arc::clone(&rom);
+        let no_pre_mine = no_pre_mine.clone();
+        let locally_hashed = &locally_hashed;
+        async move {
+            if shard.is_empty() {
+                return Vec::new();
+            }
+            let url = format!("http://{}/hash-batch", peer.addr);
+            let sent = HTTP_CLIENT
+                .post(&url)
+                .header(FORWARDED_HEADER, "1")
+                .json(&BatchHashRequest { preimages: shard.clone(), no_pre_mine })
+                .send()
+                .await;
+            let hash_locally = || {
+                locally_hashed.fetch_add(shard.len() as u64, Ordering::Relaxed);
+                shard
+                    .par_iter()
+                    .map(|p| hex::encode(sh_hash(p.as_bytes(), &fallback_rom, 8, 256)))
+                    .collect::<Vec<String>>()
+            };
+            match sent {
+                Ok(resp) if !resp.status().is_success() => {
+                    warn!("peer {} returned {} for a shard of {}, hashing shard locally", peer.addr, resp.status(), shard.len());
+                    hash_locally()
+                }
+                Ok(resp) => match resp.json::<BatchHashResponse>().await {
+                    Ok(parsed) if parsed.hashes.len() == shard.len() => parsed.hashes,
+                    Ok(parsed) => {
+                        warn!(
+                            "peer {} returned {} hashes for a shard of {} (mismatched length), hashing shard locally",
+                            peer.addr, parsed.hashes.len(), shard.len()
+                        );
+                        hash_locally()
+                    }
+                    Err(e) => {
+                        warn!("peer {} returned an invalid batch response ({}), hashing shard locally", peer.addr, e);
+                        hash_locally()
+                    }
+                },
+                Err(e) => {
+                    warn!("peer {} unreachable ({}), hashing shard locally", peer.addr, e);
+                    hash_locally()
+                }
+            }
+        }
+    });
+
+    let (mut hashes, remote_results) = tokio::join!(local_fut, futures::future::join_all(remote_futs));
+    for mut shard_hashes in remote_results {
+        hashes.append(&mut shard_hashes);
+    }
+
+    let locally_hashed = locally_hashed.load(Ordering::Relaxed) as usize;
+    (HttpResponse::Ok().json(BatchHashResponse { hashes }), locally_hashed)
+}
+
+/// POST /init - Return the cached ROM for this challenge if we already built one, otherwise
+/// kick off construction and return immediately. `Rom::new` (5-10s) runs on a blocking thread
+/// pool so it never parks the async reactor; the new ROM is built into a local `Arc` and only
+/// inserted into the cache once fully ready, so in-flight /hash calls for other challenges are
+/// never affected. A second /init for a challenge already building is handed the in-flight
+/// job's id rather than starting its own redundant build. Poll /health for the job's
+/// completion state.
 async fn init_handler(req: web::Json<InitRequest>) -> HttpResponse {
     info!("POST /init request received");
     info!("no_pre_mine: {}...", &req.no_pre_mine[..16.min(req.no_pre_mine.len())]);
 
-    let no_pre_mine_bytes = req.no_pre_mine.as_bytes();
+    let key = RomKey {
+        no_pre_mine: req.no_pre_mine.clone(),
+        pre_size: req.ash_config.pre_size,
+        rom_size: req.ash_config.rom_size,
+        mixing_numbers: req.ash_config.mixing_numbers,
+    };
+    let no_pre_mine_echo = format!("{}...", &req.no_pre_mine[..16.min(req.no_pre_mine.len())]);
+    let job_id = NEXT_INIT_JOB_ID.fetch_add(1, Ordering::SeqCst);
+
+    if ROM_CACHE.lock().await.contains(&key) {
+        info!("Challenge already cached, reusing existing ROM (job {})", job_id);
+        ROM_INIT_JOBS.write().unwrap().put(job_id, RomInitStatus::Ready);
+        return HttpResponse::Ok().json(InitResponse {
+            status: "ready".to_string(),
+            worker_pid: std::process::id(),
+            no_pre_mine: no_pre_mine_echo,
+            job_id,
+        });
+    }
 
-    // Check if ROM already initialized with different no_pre_mine
+    // Reserve this challenge before doing any building, so a second /init for the same key
+    // arriving while the first is still in flight polls the existing job instead of kicking
+    // off a redundant build. Held across the ROM_CACHE re-check (a build that finished
+    // between the check above and here would already have been removed from this map).
     {
-        let rom_lock = ROM.read().unwrap();
-        if rom_lock.is_some() {
-            warn!("ROM already initialized, reinitializing for new challenge...");
+        let mut in_flight = ROM_BUILDS_IN_FLIGHT.lock().await;
+        if let Some(&existing_job_id) = in_flight.get(&key) {
+            info!("Challenge already building (job {}), not starting a redundant build", existing_job_id);
+            // job_id itself is discarded here (we point the caller at existing_job_id
+            // instead), so it must never be written to ROM_INIT_JOBS: nothing will ever
+            // transition it out of "pending", and being the most-recently-used entry in a
+            // bounded LRU it could evict the existing_job_id entry callers were told to poll.
+            return HttpResponse::Accepted().json(InitResponse {
+                status: "pending".to_string(),
+                worker_pid: std::process::id(),
+                no_pre_mine: no_pre_mine_echo,
+                job_id: existing_job_id,
+            });
+        }
+        if ROM_CACHE.lock().await.contains(&key) {
+            info!("Challenge finished building meanwhile, reusing existing ROM (job {})", job_id);
+            ROM_INIT_JOBS.write().unwrap().put(job_id, RomInitStatus::Ready);
+            return HttpResponse::Ok().json(InitResponse {
+                status: "ready".to_string(),
+                worker_pid: std::process::id(),
+                no_pre_mine: no_pre_mine_echo,
+                job_id,
+            });
         }
+        in_flight.insert(key.clone(), job_id);
     }
 
-    info!("Starting ROM initialization (this may take 5-10 seconds)...");
-    let start = std::time::Instant::now();
+    ROM_INIT_JOBS.write().unwrap().put(job_id, RomInitStatus::Pending);
+    info!("Starting ROM initialization in the background (job {}, this may take 5-10 seconds)...", job_id);
 
-    // Create ROM using TwoStep generation
-    let rom = Rom::new(
-        no_pre_mine_bytes,
-        RomGenerationType::TwoStep {
-            pre_size: req.ash_config.pre_size as usize,
-            mixing_numbers: req.ash_config.mixing_numbers as usize,
-        },
-        req.ash_config.rom_size as usize,
-    );
+    let no_pre_mine_bytes = req.no_pre_mine.as_bytes().to_vec();
+    let pre_size = key.pre_size as usize;
+    let mixing_numbers = key.mixing_numbers as usize;
+    let rom_size = key.rom_size as usize;
 
-    let elapsed = start.elapsed().as_secs_f64();
+    tokio::spawn(async move {
+        let start = std::time::Instant::now();
+        let built = tokio::task::spawn_blocking(move || {
+            Rom::new(
+                &no_pre_mine_bytes,
+                RomGenerationType::TwoStep { pre_size, mixing_numbers },
+                rom_size,
+            )
+        })
+        .await;
 
-    // Store ROM in global state (replace if already exists)
-    let rom_arc = Arc::new(rom);
-    {
-        let mut rom_lock = ROM.write().unwrap();
-        *rom_lock = Some(rom_arc);
-    }
+        match built {
+            Ok(rom) => {
+                let elapsed = start.elapsed().as_secs_f64();
 
-    info!("✓ ROM initialized in {:.1}s", elapsed);
+                // Only goes into the cache once fully built, so a concurrent /hash never
+                // observes a half-constructed ROM. Inserting past capacity evicts whichever
+                // cached challenge was least recently used. Cache insert happens before the
+                // in-flight entry is cleared, so a racing /init always sees one or the other
+                // and never slips through both checks to start a redundant build.
+                let mut cache = ROM_CACHE.lock().await;
+                let evicts = cache.len() == cache.cap().get() && !cache.contains(&key);
+                cache.put(key.clone(), Arc::new(rom));
+                drop(cache);
+                if evicts {
+                    debug!("ROM cache over capacity, evicted least-recently-used challenge");
+                }
+                ROM_BUILDS_IN_FLIGHT.lock().await.remove(&key);
+                ROM_INIT_JOBS.write().unwrap().put(job_id, RomInitStatus::Ready);
 
-    HttpResponse::Ok().json(InitResponse {
-        status: "initialized".to_string(),
+                metrics::histogram!("hashengine_rom_init_duration_seconds").record(elapsed);
+                metrics::gauge!("hashengine_rom_initialized").set(1.0);
+
+                info!("✓ ROM initialized in {:.1}s (job {})", elapsed, job_id);
+            }
+            Err(e) => {
+                error!("ROM initialization (job {}) failed: {}", job_id, e);
+                ROM_BUILDS_IN_FLIGHT.lock().await.remove(&key);
+                ROM_INIT_JOBS.write().unwrap().put(job_id, RomInitStatus::Failed);
+            }
+        }
+    });
+
+    HttpResponse::Accepted().json(InitResponse {
+        status: "pending".to_string(),
         worker_pid: std::process::id(),
-        no_pre_mine: format!("{}...", &req.no_pre_mine[..16.min(req.no_pre_mine.len())]),
+        no_pre_mine: no_pre_mine_echo,
+        job_id,
     })
 }
 
 /// POST /hash - Hash single preimage
 async fn hash_handler(req: web::Json<HashRequest>) -> HttpResponse {
-    let rom_lock = ROM.read().unwrap();
-    let rom = match rom_lock.as_ref() {
-        Some(r) => Arc::clone(r),
-        None => {
-            error!("ROM not initialized");
+    let request_start = std::time::Instant::now();
+
+    let rom = match rom_for_no_pre_mine(&req.no_pre_mine).await {
+        RomLookup::Found(r) => r,
+        RomLookup::NotFound => {
+            error!("No cached ROM for this no_pre_mine");
             return HttpResponse::ServiceUnavailable().json(ErrorResponse {
-                error: "ROM not initialized. Call /init first.".to_string(),
+                error: "No ROM cached for this no_pre_mine. Call /init first.".to_string(),
+            });
+        }
+        RomLookup::Ambiguous => {
+            error!("Ambiguous no_pre_mine: multiple cached challenges share it with different configs");
+            return HttpResponse::Conflict().json(ErrorResponse {
+                error: "Multiple cached challenges share this no_pre_mine with different AshConfigs. \
+                        Disambiguate by clearing one from the cache before hashing."
+                    .to_string(),
             });
         }
     };
-    drop(rom_lock); // Release read lock
 
     let salt = req.preimage.as_bytes();
     let hash_bytes = sh_hash(salt, &rom, 8, 256);
     let hash_hex = hex::encode(hash_bytes);
 
+    metrics::counter!("hashengine_hashes_total").increment(1);
+    metrics::histogram!("hashengine_request_duration_seconds", "handler" => "hash")
+        .record(request_start.elapsed().as_secs_f64());
+
     HttpResponse::Ok().json(HashResponse {
         hash: hash_hex,
     })
 }
 
-/// POST /hash-batch - Hash multiple preimages in parallel
-async fn hash_batch_handler(req: web::Json<BatchHashRequest>) -> HttpResponse {
+/// POST /hash-batch - Hash multiple preimages in parallel, sharding across any cluster
+/// peers gossiped to be working the same challenge (unless this is itself a forwarded
+/// sub-request, in which case the shard is always hashed locally).
+async fn hash_batch_handler(req: web::Json<BatchHashRequest>, http_req: HttpRequest) -> HttpResponse {
     let batch_start = std::time::Instant::now();
 
-    let rom_lock = ROM.read().unwrap();
-    let rom = match rom_lock.as_ref() {
-        Some(r) => Arc::clone(r),
-        None => {
-            error!("ROM not initialized");
+    let rom = match rom_for_no_pre_mine(&req.no_pre_mine).await {
+        RomLookup::Found(r) => r,
+        RomLookup::NotFound => {
+            error!("No cached ROM for this no_pre_mine");
             return HttpResponse::ServiceUnavailable().json(ErrorResponse {
-                error: "ROM not initialized. Call /init first.".to_string(),
+                error: "No ROM cached for this no_pre_mine. Call /init first.".to_string(),
+            });
+        }
+        RomLookup::Ambiguous => {
+            error!("Ambiguous no_pre_mine: multiple cached challenges share it with different configs");
+            return HttpResponse::Conflict().json(ErrorResponse {
+                error: "Multiple cached challenges share this no_pre_mine with different AshConfigs. \
+                        Disambiguate by clearing one from the cache before hashing."
+                    .to_string(),
             });
         }
     };
-    drop(rom_lock); // Release read lock
 
     if req.preimages.is_empty() {
         return HttpResponse::BadRequest().json(ErrorResponse {
@@ -179,6 +789,28 @@ async fn hash_batch_handler(req: web::Json<BatchHashRequest>) -> HttpResponse {
     }
 
     let preimage_count = req.preimages.len();
+    let is_forwarded = http_req.headers().contains_key(FORWARDED_HEADER);
+
+    if !is_forwarded {
+        let self_addr = format!(
+            "{}:{}",
+            std::env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
+            std::env::var("PORT").unwrap_or_else(|_| "9001".to_string())
+        );
+        let peers = matching_peers(&self_addr, &req.no_pre_mine);
+        if !peers.is_empty() {
+            debug!("sharding batch of {} preimages across {} peer(s)", preimage_count, peers.len());
+            let (response, locally_hashed) = shard_and_dispatch(req.preimages.clone(), req.no_pre_mine.clone(), rom, peers).await;
+            // Only count the hashes this node actually computed: shards a peer processed
+            // successfully are counted by that peer's own handler when it runs the
+            // forwarded sub-request, so counting the full batch here would double-count them.
+            metrics::counter!("hashengine_hashes_total").increment(locally_hashed as u64);
+            metrics::histogram!("hashengine_batch_size").record(preimage_count as f64);
+            metrics::histogram!("hashengine_request_duration_seconds", "handler" => "hash_batch")
+                .record(batch_start.elapsed().as_secs_f64());
+            return response;
+        }
+    }
 
     // Parallel hash processing using rayon with pre-allocated result vector
     // Each preimage is hashed on a separate thread
@@ -204,6 +836,11 @@ async fn hash_batch_handler(req: web::Json<BatchHashRequest>) -> HttpResponse {
         );
     }
 
+    metrics::counter!("hashengine_hashes_total").increment(preimage_count as u64);
+    metrics::histogram!("hashengine_batch_size").record(preimage_count as f64);
+    metrics::histogram!("hashengine_request_duration_seconds", "handler" => "hash_batch")
+        .record(total_duration.as_secs_f64());
+
     HttpResponse::Ok().json(BatchHashResponse { hashes })
 }
 
@@ -224,17 +861,32 @@ async fn hash_batch_shared_handler(req: web::Json<serde_json::Value>) -> HttpRes
         }
     };
 
-    let rom_lock = ROM.read().unwrap();
-    let rom = match rom_lock.as_ref() {
-        Some(r) => Arc::clone(r),
+    let no_pre_mine = match req.get("no_pre_mine").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
         None => {
-            error!("ROM not initialized");
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "no_pre_mine is required".to_string(),
+            });
+        }
+    };
+
+    let rom = match rom_for_no_pre_mine(&no_pre_mine).await {
+        RomLookup::Found(r) => r,
+        RomLookup::NotFound => {
+            error!("No cached ROM for this no_pre_mine");
             return HttpResponse::ServiceUnavailable().json(ErrorResponse {
-                error: "ROM not initialized. Call /init first.".to_string(),
+                error: "No ROM cached for this no_pre_mine. Call /init first.".to_string(),
+            });
+        }
+        RomLookup::Ambiguous => {
+            error!("Ambiguous no_pre_mine: multiple cached challenges share it with different configs");
+            return HttpResponse::Conflict().json(ErrorResponse {
+                error: "Multiple cached challenges share this no_pre_mine with different AshConfigs. \
+                        Disambiguate by clearing one from the cache before hashing."
+                    .to_string(),
             });
         }
     };
-    drop(rom_lock); // Release read lock
 
     if preimages.is_empty() {
         return HttpResponse::BadRequest().json(ErrorResponse {
@@ -266,26 +918,54 @@ async fn hash_batch_shared_handler(req: web::Json<serde_json::Value>) -> HttpRes
         );
     }
 
+    metrics::counter!("hashengine_hashes_total").increment(preimage_count as u64);
+    metrics::histogram!("hashengine_batch_size").record(preimage_count as f64);
+    metrics::histogram!("hashengine_request_duration_seconds", "handler" => "hash_batch_shared")
+        .record(total_duration.as_secs_f64());
+
     // Return standard response (SharedArrayBuffer handled on Node.js side)
     HttpResponse::Ok().json(BatchHashResponse { hashes })
 }
 
 /// GET /health - Health check endpoint
 async fn health_handler() -> HttpResponse {
-    let rom_lock = ROM.read().unwrap();
-    let rom_initialized = rom_lock.is_some();
-    drop(rom_lock);
+    let cache = ROM_CACHE.lock().await;
+    let rom_cache_size = cache.len();
+    let rom_cache_capacity = cache.cap().get();
+    let cached_challenges: Vec<ChallengeId> =
+        cache.iter().map(|(key, _)| challenge_id_for(key)).collect();
+    drop(cache);
+
+    let mut init_jobs: Vec<InitJobStatus> = ROM_INIT_JOBS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(job_id, status)| InitJobStatus {
+            job_id: *job_id,
+            status: status.as_str().to_string(),
+        })
+        .collect();
+    init_jobs.sort_by_key(|j| j.job_id);
 
     HttpResponse::Ok().json(HealthResponse {
         status: "ok".to_string(),
-        rom_initialized,
+        rom_initialized: rom_cache_size > 0,
         native_available: true,
         config: None,
-        no_pre_mine_first8: None,
-        no_pre_mine_last8: None,
+        rom_cache_size,
+        rom_cache_capacity,
+        cached_challenges,
+        init_jobs,
     })
 }
 
+/// GET /metrics - Prometheus text-format scrape endpoint
+async fn metrics_handler(handle: web::Data<PrometheusHandle>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(handle.render())
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize logger
@@ -306,14 +986,47 @@ async fn main() -> std::io::Result<()> {
     info!("Parallel processing: rayon thread pool");
     info!("═══════════════════════════════════════════════════════════");
 
-    HttpServer::new(|| {
+    let prometheus_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+        .install_recorder()
+        .expect("failed to install Prometheus recorder");
+    metrics::describe_counter!("hashengine_hashes_total", "Total number of preimages hashed");
+    metrics::describe_histogram!("hashengine_batch_size", "Size of /hash-batch* requests");
+    metrics::describe_histogram!(
+        "hashengine_request_duration_seconds",
+        "Handler duration in seconds, labeled by `handler`"
+    );
+    metrics::describe_gauge!("hashengine_rom_initialized", "1 once a ROM has been initialized, 0 otherwise");
+    metrics::describe_histogram!("hashengine_rom_init_duration_seconds", "Rom::new duration in seconds");
+
+    let seeds: Vec<String> = std::env::var("PEERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if !seeds.is_empty() {
+        let self_addr = format!("{}:{}", host, port);
+        let gossip_bind = gossip_addr_for(&self_addr).expect("HOST:PORT must be host:port");
+        info!("Gossip cluster enabled: {} seed peer(s), gossip listening on {}", seeds.len(), gossip_bind);
+        match tokio::net::UdpSocket::bind(&gossip_bind).await {
+            Ok(socket) => {
+                tokio::spawn(run_gossip(self_addr, seeds, socket));
+            }
+            Err(e) => error!("failed to bind gossip socket on {}: {}", gossip_bind, e),
+        }
+    }
+
+    HttpServer::new(move || {
         App::new()
+            .app_data(web::Data::new(prometheus_handle.clone()))
             // Logger middleware removed - only log important events via RUST_LOG
             .route("/init", web::post().to(init_handler))
             .route("/hash", web::post().to(hash_handler))
             .route("/hash-batch", web::post().to(hash_batch_handler))
             .route("/hash-batch-shared", web::post().to(hash_batch_shared_handler))
             .route("/health", web::get().to(health_handler))
+            .route("/metrics", web::get().to(metrics_handler))
     })
     .workers(workers)
     .bind(format!("{}:{}", host, port))?